@@ -1,29 +1,112 @@
-use crate::transaction::{Account, Transaction, TransactionType};
-use anyhow::{anyhow, bail, Result};
+use crate::error::EngineError;
+use crate::store::{AccountStore, MemAccountStore, MemStore, TransactionStore};
+use crate::transaction::{
+    Account, AccountOutput, ClientId, Rounding, Transaction, TransactionType,
+};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle state of a stored transaction.
+///
+/// Only the transitions `Processed -> Disputed`, `Disputed -> Resolved`,
+/// `Disputed -> ChargedBack` and `Resolved -> Disputed` (a resolved
+/// transaction may be disputed again) are legal. `ChargedBack` is terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum TxState {
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl TxState {
+    /// Move to `Disputed`, rejecting any illegal source state.
+    fn dispute(&mut self) -> Result<(), EngineError> {
+        match self {
+            TxState::Processed | TxState::Resolved => {
+                *self = TxState::Disputed;
+                Ok(())
+            }
+            TxState::Disputed => Err(EngineError::AlreadyDisputed),
+            TxState::ChargedBack => Err(EngineError::TerminalState),
+        }
+    }
+
+    /// Move to `Resolved`, rejecting any illegal source state.
+    fn resolve(&mut self) -> Result<(), EngineError> {
+        match self {
+            TxState::Disputed => {
+                *self = TxState::Resolved;
+                Ok(())
+            }
+            _ => Err(EngineError::NotDisputed),
+        }
+    }
+
+    /// Move to `ChargedBack`, rejecting any illegal source state.
+    fn chargeback(&mut self) -> Result<(), EngineError> {
+        match self {
+            TxState::Disputed => {
+                *self = TxState::ChargedBack;
+                Ok(())
+            }
+            _ => Err(EngineError::NotDisputed),
+        }
+    }
+}
 
 /// Stored transaction for dispute handling
-#[derive(Debug, Clone)]
-struct StoredTransaction {
-    amount: Decimal,
-    client: u16,
-    disputed: bool,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredTransaction {
+    pub(crate) amount: Decimal,
+    pub(crate) client: u16,
+    pub(crate) state: TxState,
+    /// Whether the original movement was a deposit or a withdrawal; dispute
+    /// arithmetic differs between the two.
+    pub(crate) kind: TransactionType,
+}
+
+/// Main transaction processing engine.
+///
+/// Generic over a [`TransactionStore`] (which holds the potentially huge
+/// history consulted only by disputes) and an [`AccountStore`] (the small,
+/// always-hot set of client balances). The default backends keep everything
+/// in memory; see [`TransactionEngine::with_stores`] for disk-spilling.
+pub struct TransactionEngine<T = MemStore, A = MemAccountStore> {
+    accounts: A,
+    transactions: T,
+    rounding: Rounding,
 }
 
-/// Main transaction processing engine
-#[derive(Default)]
-pub struct TransactionEngine {
-    accounts: HashMap<u16, Account>,
-    transactions: HashMap<u32, StoredTransaction>,
+impl Default for TransactionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl TransactionEngine {
+impl TransactionEngine<MemStore, MemAccountStore> {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_stores(MemStore::default(), MemAccountStore::default())
+    }
+}
+
+impl<T: TransactionStore, A: AccountStore> TransactionEngine<T, A> {
+    /// Build an engine over explicit storage backends.
+    pub fn with_stores(transactions: T, accounts: A) -> Self {
+        Self {
+            accounts,
+            transactions,
+            rounding: Rounding::default(),
+        }
+    }
+
+    /// Set the rounding strategy applied to incoming amounts.
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
     }
 
-    pub fn process(&mut self, transaction: Transaction) -> Result<()> {
+    pub fn process(&mut self, transaction: Transaction) -> Result<(), EngineError> {
         match transaction.tx_type {
             TransactionType::Deposit => self.process_deposit(transaction),
             TransactionType::Withdrawal => self.process_withdrawal(transaction),
@@ -33,23 +116,18 @@ impl TransactionEngine {
         }
     }
 
-    fn process_deposit(&mut self, tx: Transaction) -> Result<()> {
-        let amount = tx
-            .amount
-            .ok_or_else(|| anyhow!("Deposit requires amount"))?;
+    fn process_deposit(&mut self, tx: Transaction) -> Result<(), EngineError> {
+        let amount = self.rounding.apply(tx.amount.ok_or(EngineError::MissingAmount)?);
 
-        let account = self
-            .accounts
-            .entry(tx.client)
-            .or_insert_with(|| Account::new(tx.client));
+        let account = self.accounts.get_or_create(tx.client);
 
         if account.locked {
-            bail!("Account is locked");
+            return Err(EngineError::AccountLocked);
         }
 
         // Check for duplicate transaction ID
-        if self.transactions.contains_key(&tx.tx) {
-            bail!("Duplicate transaction ID");
+        if self.transactions.contains(tx.tx) {
+            return Err(EngineError::DuplicateTx(tx.tx));
         }
 
         account.deposit(amount);
@@ -60,30 +138,26 @@ impl TransactionEngine {
             StoredTransaction {
                 client: tx.client,
                 amount,
-                disputed: false,
+                state: TxState::Processed,
+                kind: TransactionType::Deposit,
             },
-        );
+        )?;
 
         Ok(())
     }
 
-    fn process_withdrawal(&mut self, tx: Transaction) -> Result<()> {
-        let amount = tx
-            .amount
-            .ok_or_else(|| anyhow!("Withdrawal requires amount"))?;
+    fn process_withdrawal(&mut self, tx: Transaction) -> Result<(), EngineError> {
+        let amount = self.rounding.apply(tx.amount.ok_or(EngineError::MissingAmount)?);
 
-        let account = self
-            .accounts
-            .entry(tx.client)
-            .or_insert_with(|| Account::new(tx.client));
+        let account = self.accounts.get_or_create(tx.client);
 
         if account.locked {
-            bail!("Account is locked");
+            return Err(EngineError::AccountLocked);
         }
 
         // Check for duplicate transaction ID
-        if self.transactions.contains_key(&tx.tx) {
-            bail!("Duplicate transaction ID");
+        if self.transactions.contains(tx.tx) {
+            return Err(EngineError::DuplicateTx(tx.tx));
         }
 
         account.withdraw(amount)?;
@@ -94,93 +168,123 @@ impl TransactionEngine {
             StoredTransaction {
                 client: tx.client,
                 amount,
-                disputed: false,
+                state: TxState::Processed,
+                kind: TransactionType::Withdrawal,
             },
-        );
+        )?;
 
         Ok(())
     }
 
-    fn process_dispute(&mut self, tx: Transaction) -> Result<()> {
-        let stored = self
+    fn process_dispute(&mut self, tx: Transaction) -> Result<(), EngineError> {
+        let mut stored = self
             .transactions
-            .get_mut(&tx.tx)
-            .ok_or_else(|| anyhow!("Transaction not found"))?;
+            .get(tx.tx)?
+            .ok_or(EngineError::UnknownTx(tx.tx))?;
 
         if stored.client != tx.client {
-            bail!("Transaction belongs to different client");
+            return Err(EngineError::ClientMismatch);
         }
 
-        if stored.disputed {
-            bail!("Transaction already disputed");
-        }
+        stored.state.dispute()?;
 
-        stored.disputed = true;
+        // Persist the new state before touching balances: if the write fails
+        // under the disk backend, the account is left untouched so the dispute
+        // can be retried without double-holding funds.
+        self.transactions.update(tx.tx, &stored)?;
 
         let account = self
             .accounts
-            .get_mut(&tx.client)
-            .ok_or_else(|| anyhow!("Account not found"))?;
+            .get_mut(tx.client)
+            .ok_or(EngineError::AccountNotFound(tx.client))?;
 
-        account.dispute(stored.amount);
+        match stored.kind {
+            TransactionType::Withdrawal => account.dispute_withdrawal(stored.amount),
+            _ => account.dispute(stored.amount),
+        }
 
         Ok(())
     }
 
-    fn process_resolve(&mut self, tx: Transaction) -> Result<()> {
-        let stored = self
+    fn process_resolve(&mut self, tx: Transaction) -> Result<(), EngineError> {
+        let mut stored = self
             .transactions
-            .get_mut(&tx.tx)
-            .ok_or_else(|| anyhow!("Transaction not found"))?;
+            .get(tx.tx)?
+            .ok_or(EngineError::UnknownTx(tx.tx))?;
 
         if stored.client != tx.client {
-            bail!("Transaction belongs to different client");
+            return Err(EngineError::ClientMismatch);
         }
 
-        if !stored.disputed {
-            bail!("Transaction not under dispute");
-        }
+        stored.state.resolve()?;
 
-        stored.disputed = false;
+        // Persist before mutating balances; see `process_dispute`.
+        self.transactions.update(tx.tx, &stored)?;
 
         let account = self
             .accounts
-            .get_mut(&tx.client)
-            .ok_or_else(|| anyhow!("Account not found"))?;
+            .get_mut(tx.client)
+            .ok_or(EngineError::AccountNotFound(tx.client))?;
 
-        account.resolve(stored.amount);
+        match stored.kind {
+            TransactionType::Withdrawal => account.resolve_withdrawal(stored.amount),
+            _ => account.resolve(stored.amount),
+        }
 
         Ok(())
     }
 
-    fn process_chargeback(&mut self, tx: Transaction) -> Result<()> {
-        let stored = self
+    fn process_chargeback(&mut self, tx: Transaction) -> Result<(), EngineError> {
+        let mut stored = self
             .transactions
-            .get(&tx.tx)
-            .ok_or_else(|| anyhow!("Transaction not found"))?;
+            .get(tx.tx)?
+            .ok_or(EngineError::UnknownTx(tx.tx))?;
 
         if stored.client != tx.client {
-            bail!("Transaction belongs to different client");
+            return Err(EngineError::ClientMismatch);
         }
 
-        if !stored.disputed {
-            bail!("Transaction not under dispute");
-        }
+        stored.state.chargeback()?;
 
         let amount = stored.amount;
 
+        // Persist before mutating balances; see `process_dispute`.
+        self.transactions.update(tx.tx, &stored)?;
+
         let account = self
             .accounts
-            .get_mut(&tx.client)
-            .ok_or_else(|| anyhow!("Account not found"))?;
+            .get_mut(tx.client)
+            .ok_or(EngineError::AccountNotFound(tx.client))?;
 
-        account.chargeback(amount);
+        match stored.kind {
+            TransactionType::Withdrawal => account.chargeback_withdrawal(amount),
+            _ => account.chargeback(amount),
+        }
 
         Ok(())
     }
 
     pub fn get_accounts(&self) -> Vec<Account> {
-        self.accounts.values().cloned().collect()
+        self.accounts.accounts()
+    }
+
+    /// Snapshot every account as an [`AccountOutput`] carrying its client id,
+    /// in no particular order.
+    pub fn account_outputs(&self) -> Vec<AccountOutput> {
+        self.accounts
+            .snapshot()
+            .iter()
+            .map(|(client, account)| AccountOutput::new(ClientId(*client), account))
+            .collect()
+    }
+
+    /// The [`AccountOutput`] for a single client, if that client exists.
+    pub fn account_output(&self, client: u16) -> Option<AccountOutput> {
+        self.accounts
+            .snapshot()
+            .iter()
+            .find(|(id, _)| *id == client)
+            .map(|(id, account)| AccountOutput::new(ClientId(*id), account))
     }
 }
 
@@ -336,4 +440,227 @@ mod tests {
         assert_eq!(accounts[0].total, Decimal::ZERO);
         assert!(accounts[0].locked);
     }
+
+    #[test]
+    fn test_double_dispute_rejected() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::new(100, 1)),
+            })
+            .unwrap();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Dispute,
+                client: 1,
+                tx: 1,
+                amount: None,
+            })
+            .unwrap();
+
+        let result = engine.process(Transaction {
+            tx_type: TransactionType::Dispute,
+            client: 1,
+            tx: 1,
+            amount: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_dispute_after_chargeback_rejected() {
+        let mut engine = TransactionEngine::new();
+
+        for tx_type in [
+            TransactionType::Deposit,
+            TransactionType::Dispute,
+            TransactionType::Chargeback,
+        ] {
+            engine
+                .process(Transaction {
+                    tx_type,
+                    client: 1,
+                    tx: 1,
+                    amount: matches!(tx_type, TransactionType::Deposit)
+                        .then(|| Decimal::new(100, 1)),
+                })
+                .unwrap();
+        }
+
+        // Charged back is terminal: it may not be disputed again.
+        let result = engine.process(Transaction {
+            tx_type: TransactionType::Dispute,
+            client: 1,
+            tx: 1,
+            amount: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_resolve() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::new(100, 1)), // 10.0
+            })
+            .unwrap();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Withdrawal,
+                client: 1,
+                tx: 2,
+                amount: Some(Decimal::new(40, 1)), // 4.0
+            })
+            .unwrap();
+
+        // Disputing the withdrawal provisionally restores the funds as held.
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Dispute,
+                client: 1,
+                tx: 2,
+                amount: None,
+            })
+            .unwrap();
+
+        let accounts = engine.get_accounts();
+        assert_eq!(accounts[0].available, Decimal::new(60, 1));
+        assert_eq!(accounts[0].held, Decimal::new(40, 1));
+        assert_eq!(accounts[0].total, Decimal::new(100, 1));
+
+        // Resolving lets the withdrawal stand.
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Resolve,
+                client: 1,
+                tx: 2,
+                amount: None,
+            })
+            .unwrap();
+
+        let accounts = engine.get_accounts();
+        assert_eq!(accounts[0].available, Decimal::new(60, 1));
+        assert_eq!(accounts[0].held, Decimal::ZERO);
+        assert_eq!(accounts[0].total, Decimal::new(60, 1));
+        assert!(!accounts[0].locked);
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_chargeback() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::new(100, 1)),
+            })
+            .unwrap();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Withdrawal,
+                client: 1,
+                tx: 2,
+                amount: Some(Decimal::new(40, 1)),
+            })
+            .unwrap();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Dispute,
+                client: 1,
+                tx: 2,
+                amount: None,
+            })
+            .unwrap();
+
+        // Charging back finalizes the reversal: the withdrawn funds return to
+        // available and the account is frozen.
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Chargeback,
+                client: 1,
+                tx: 2,
+                amount: None,
+            })
+            .unwrap();
+
+        let accounts = engine.get_accounts();
+        assert_eq!(accounts[0].available, Decimal::new(100, 1));
+        assert_eq!(accounts[0].held, Decimal::ZERO);
+        assert_eq!(accounts[0].total, Decimal::new(100, 1));
+        assert!(accounts[0].locked);
+    }
+
+    #[test]
+    fn test_four_decimal_rounding() {
+        let mut engine = TransactionEngine::new();
+
+        // 1.00005 rounds half-up to four places -> 1.0001.
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::new(100005, 5)),
+            })
+            .unwrap();
+
+        let output = engine.account_output(1).unwrap();
+        assert_eq!(output.available.to_string(), "1.0001");
+        assert_eq!(output.total.to_string(), "1.0001");
+
+        // Withdrawing the extra 0.0001 leaves a clean 1.0000.
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Withdrawal,
+                client: 1,
+                tx: 2,
+                amount: Some(Decimal::new(1, 4)),
+            })
+            .unwrap();
+
+        let output = engine.account_output(1).unwrap();
+        assert_eq!(output.available.to_string(), "1.0000");
+        assert_eq!(output.held.to_string(), "0.0000");
+        assert_eq!(output.total.to_string(), "1.0000");
+    }
+
+    #[test]
+    fn test_resolve_without_dispute_rejected() {
+        let mut engine = TransactionEngine::new();
+
+        engine
+            .process(Transaction {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::new(100, 1)),
+            })
+            .unwrap();
+
+        let result = engine.process(Transaction {
+            tx_type: TransactionType::Resolve,
+            client: 1,
+            tx: 1,
+            amount: None,
+        });
+
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,76 @@
+//! Typed engine errors.
+//!
+//! Every fallible engine operation returns an [`EngineError`] so callers can
+//! match on the failure kind — distinguishing, say, a locked account from a
+//! duplicate id — rather than parsing strings.
+
+use thiserror::Error;
+
+/// A failure while processing a single transaction.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    /// The target account is frozen after a chargeback.
+    #[error("account is locked")]
+    AccountLocked,
+
+    /// A transaction id was reused.
+    #[error("duplicate transaction id {0}")]
+    DuplicateTx(u32),
+
+    /// A dispute/resolve/chargeback referenced an unknown transaction.
+    #[error("unknown transaction id {0}")]
+    UnknownTx(u32),
+
+    /// The referenced transaction belongs to a different client.
+    #[error("transaction belongs to a different client")]
+    ClientMismatch,
+
+    /// No account exists for the referenced client.
+    #[error("account {0} not found")]
+    AccountNotFound(u16),
+
+    /// A withdrawal exceeded the available balance.
+    #[error("insufficient funds")]
+    InsufficientFunds,
+
+    /// A deposit or withdrawal arrived without an amount.
+    #[error("transaction requires an amount")]
+    MissingAmount,
+
+    /// A transaction already under dispute was disputed again.
+    #[error("transaction is already disputed")]
+    AlreadyDisputed,
+
+    /// A resolve/chargeback referenced a transaction that is not disputed.
+    #[error("transaction is not under dispute")]
+    NotDisputed,
+
+    /// A dispute referenced a charged-back transaction, which is terminal.
+    /// Resolving or charging back such a transaction reports `NotDisputed`.
+    #[error("transaction is in a terminal state")]
+    TerminalState,
+
+    /// The storage backend failed to read or persist a record.
+    #[error("storage backend failure")]
+    Storage(#[from] anyhow::Error),
+}
+
+impl EngineError {
+    /// A stable, lower-case label grouping errors for metrics and structured
+    /// logging.
+    pub fn category(&self) -> &'static str {
+        match self {
+            EngineError::AccountLocked => "account_locked",
+            EngineError::DuplicateTx(_) => "duplicate_tx",
+            EngineError::UnknownTx(_) => "unknown_tx",
+            EngineError::ClientMismatch => "client_mismatch",
+            EngineError::AccountNotFound(_) => "account_not_found",
+            EngineError::InsufficientFunds => "insufficient_funds",
+            EngineError::MissingAmount => "missing_amount",
+            EngineError::AlreadyDisputed => "already_disputed",
+            EngineError::NotDisputed => "not_disputed",
+            EngineError::TerminalState => "terminal_state",
+            EngineError::Storage(_) => "storage",
+        }
+    }
+}
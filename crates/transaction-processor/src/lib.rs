@@ -1,4 +1,7 @@
 pub mod engine;
+pub mod error;
+pub mod parallel;
+pub mod store;
 pub mod transaction;
 
 use engine::TransactionEngine;
@@ -0,0 +1,127 @@
+//! Client-sharded parallel processing.
+//!
+//! Transactions for different clients are completely independent, so they can
+//! be processed on separate threads without any shared state. [`ParallelEngine`]
+//! spawns `N` worker threads, each owning its own private
+//! [`TransactionEngine`], and routes every transaction to a fixed shard chosen
+//! by its `client` id. Because a given client always lands on the same shard
+//! and each shard drains its channel in FIFO order, ordering is preserved
+//! *within* a client (a dispute can never race ahead of its deposit) while
+//! different clients run concurrently.
+
+use crate::engine::TransactionEngine;
+use crate::transaction::{AccountOutput, Rounding, Transaction};
+use std::collections::HashMap;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{self, JoinHandle};
+use tracing::warn;
+
+/// Bound on each worker's inbound queue, providing backpressure so a slow shard
+/// cannot let the channel grow without limit.
+const CHANNEL_BOUND: usize = 1024;
+
+/// Aggregate outcome of a worker: its final accounts plus how many transactions
+/// it accepted and rejected, broken down by failure category.
+struct ShardResult {
+    accounts: Vec<AccountOutput>,
+    processed: u64,
+    skipped: u64,
+    skipped_by_category: HashMap<&'static str, u64>,
+}
+
+/// A transaction engine that fans work out across client-partitioned shards.
+pub struct ParallelEngine {
+    senders: Vec<SyncSender<Transaction>>,
+    workers: Vec<JoinHandle<ShardResult>>,
+}
+
+impl ParallelEngine {
+    /// Spawn `threads` worker shards (at least one), each applying `rounding`
+    /// to incoming amounts.
+    pub fn new(threads: usize, rounding: Rounding) -> Self {
+        let threads = threads.max(1);
+        let mut senders = Vec::with_capacity(threads);
+        let mut workers = Vec::with_capacity(threads);
+
+        for shard in 0..threads {
+            let (tx, rx) = sync_channel::<Transaction>(CHANNEL_BOUND);
+            senders.push(tx);
+            workers.push(
+                thread::Builder::new()
+                    .name(format!("shard-{shard}"))
+                    .spawn(move || {
+                        let mut engine = TransactionEngine::new().with_rounding(rounding);
+                        let mut processed = 0;
+                        let mut skipped = 0;
+                        let mut skipped_by_category: HashMap<&'static str, u64> = HashMap::new();
+
+                        // FIFO drain preserves per-client ordering.
+                        for transaction in rx {
+                            if let Err(e) = engine.process(transaction) {
+                                warn!(category = e.category(), "Transaction processing error: {}", e);
+                                skipped += 1;
+                                *skipped_by_category.entry(e.category()).or_default() += 1;
+                            } else {
+                                processed += 1;
+                            }
+                        }
+
+                        ShardResult {
+                            accounts: engine.account_outputs(),
+                            processed,
+                            skipped,
+                            skipped_by_category,
+                        }
+                    })
+                    .expect("failed to spawn worker thread"),
+            );
+        }
+
+        Self { senders, workers }
+    }
+
+    /// Route a transaction to the shard owning its client.
+    pub fn submit(&self, transaction: Transaction) {
+        let shard = transaction.client as usize % self.senders.len();
+        // A worker only stops once every sender is dropped in `finish`, so the
+        // receiver is always alive here.
+        let _ = self.senders[shard].send(transaction);
+    }
+
+    /// Close the input channels, wait for every shard to drain, and merge their
+    /// results into the combined account set and processed/skipped counts.
+    pub fn finish(self) -> ParallelSummary {
+        // Dropping the senders signals the workers to finish.
+        drop(self.senders);
+
+        let mut accounts = Vec::new();
+        let mut processed = 0;
+        let mut skipped = 0;
+        let mut skipped_by_category: HashMap<&'static str, u64> = HashMap::new();
+
+        for worker in self.workers {
+            let result = worker.join().expect("worker thread panicked");
+            accounts.extend(result.accounts);
+            processed += result.processed;
+            skipped += result.skipped;
+            for (category, count) in result.skipped_by_category {
+                *skipped_by_category.entry(category).or_default() += count;
+            }
+        }
+
+        ParallelSummary {
+            accounts,
+            processed,
+            skipped,
+            skipped_by_category,
+        }
+    }
+}
+
+/// Merged outcome of a parallel run.
+pub struct ParallelSummary {
+    pub accounts: Vec<AccountOutput>,
+    pub processed: u64,
+    pub skipped: u64,
+    pub skipped_by_category: HashMap<&'static str, u64>,
+}
@@ -0,0 +1,162 @@
+//! Pluggable storage backends for the transaction engine.
+//!
+//! The engine touches two very differently shaped data sets: the *account*
+//! table is small (one entry per client) and mutated on every transaction,
+//! while the *transaction* history can grow to the size of the whole input
+//! yet is only ever revisited by disputes. Splitting them behind the
+//! [`AccountStore`] and [`TransactionStore`] traits lets the hot account set
+//! stay in memory while the cold history spills to disk on demand.
+
+use crate::engine::StoredTransaction;
+use crate::transaction::Account;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Storage for the processed-transaction history, keyed by transaction id.
+///
+/// Implementations may keep records in memory or on disk; the engine only
+/// relies on read-modify-write via [`get`](Self::get) and
+/// [`update`](Self::update), so a backend never has to hand out a borrow into
+/// its backing storage.
+pub trait TransactionStore {
+    /// Whether a transaction with this id has already been recorded.
+    fn contains(&self, tx: u32) -> bool;
+
+    /// Record a freshly processed transaction. Errors if the backend fails to
+    /// persist it.
+    fn insert(&mut self, tx: u32, record: StoredTransaction) -> Result<()>;
+
+    /// Fetch a copy of a stored transaction, if present.
+    fn get(&self, tx: u32) -> Result<Option<StoredTransaction>>;
+
+    /// Overwrite an existing record, typically after a state transition.
+    fn update(&mut self, tx: u32, record: &StoredTransaction) -> Result<()>;
+}
+
+/// Storage for client account balances.
+pub trait AccountStore {
+    /// Return the account for `client`, creating an empty one if needed.
+    fn get_or_create(&mut self, client: u16) -> &mut Account;
+
+    /// Return the account for `client` if it already exists.
+    fn get_mut(&mut self, client: u16) -> Option<&mut Account>;
+
+    /// Snapshot every account currently held.
+    fn accounts(&self) -> Vec<Account>;
+
+    /// Snapshot every account paired with its client id.
+    fn snapshot(&self) -> Vec<(u16, Account)>;
+}
+
+/// In-memory [`TransactionStore`] backed by a [`HashMap`]. This is the default
+/// backend and keeps the entire history resident.
+#[derive(Debug, Default)]
+pub struct MemStore {
+    transactions: HashMap<u32, StoredTransaction>,
+}
+
+impl TransactionStore for MemStore {
+    fn contains(&self, tx: u32) -> bool {
+        self.transactions.contains_key(&tx)
+    }
+
+    fn insert(&mut self, tx: u32, record: StoredTransaction) -> Result<()> {
+        self.transactions.insert(tx, record);
+        Ok(())
+    }
+
+    fn get(&self, tx: u32) -> Result<Option<StoredTransaction>> {
+        Ok(self.transactions.get(&tx).cloned())
+    }
+
+    fn update(&mut self, tx: u32, record: &StoredTransaction) -> Result<()> {
+        self.transactions.insert(tx, record.clone());
+        Ok(())
+    }
+}
+
+/// In-memory [`AccountStore`] backed by a [`HashMap`]. Accounts are always kept
+/// resident regardless of the transaction backend, since there is only one per
+/// client.
+#[derive(Debug, Default)]
+pub struct MemAccountStore {
+    accounts: HashMap<u16, Account>,
+}
+
+impl AccountStore for MemAccountStore {
+    fn get_or_create(&mut self, client: u16) -> &mut Account {
+        self.accounts.entry(client).or_default()
+    }
+
+    fn get_mut(&mut self, client: u16) -> Option<&mut Account> {
+        self.accounts.get_mut(&client)
+    }
+
+    fn accounts(&self) -> Vec<Account> {
+        self.accounts.values().cloned().collect()
+    }
+
+    fn snapshot(&self) -> Vec<(u16, Account)> {
+        self.accounts
+            .iter()
+            .map(|(client, account)| (*client, account.clone()))
+            .collect()
+    }
+}
+
+/// Disk-spilling [`TransactionStore`] built on an embedded [`sled`] key-value
+/// file. Records are keyed by big-endian transaction id and encoded as JSON, so
+/// a multi-gigabyte history survives on disk instead of exhausting RAM.
+pub struct DiskStore {
+    db: sled::Db,
+}
+
+impl DiskStore {
+    /// Open (creating if absent) a transaction store rooted at `path`.
+    ///
+    /// The store is cleared on open so each run starts from an empty history:
+    /// the backend only exists to keep a single run's history off the heap, not
+    /// to persist across runs. Replaying a stale file would otherwise reject
+    /// every re-ingested transaction as a duplicate.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = sled::open(path.as_ref())
+            .with_context(|| format!("Failed to open store at {}", path.as_ref().display()))?;
+        db.clear().context("Failed to clear store")?;
+        Ok(Self { db })
+    }
+}
+
+impl TransactionStore for DiskStore {
+    fn contains(&self, tx: u32) -> bool {
+        self.db.contains_key(tx.to_be_bytes()).unwrap_or(false)
+    }
+
+    fn insert(&mut self, tx: u32, record: StoredTransaction) -> Result<()> {
+        self.update(tx, &record)
+    }
+
+    fn get(&self, tx: u32) -> Result<Option<StoredTransaction>> {
+        let raw = self
+            .db
+            .get(tx.to_be_bytes())
+            .context("Failed to read from store")?;
+
+        match raw {
+            Some(bytes) => {
+                let record = serde_json::from_slice(&bytes)
+                    .context("Failed to decode stored transaction")?;
+                Ok(Some(record))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn update(&mut self, tx: u32, record: &StoredTransaction) -> Result<()> {
+        let bytes = serde_json::to_vec(record).context("Failed to encode stored transaction")?;
+        self.db
+            .insert(tx.to_be_bytes(), bytes)
+            .context("Failed to write to store")?;
+        Ok(())
+    }
+}
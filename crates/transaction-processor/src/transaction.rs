@@ -1,7 +1,35 @@
-use anyhow::{bail, Result};
-use rust_decimal::Decimal;
+use crate::error::EngineError;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 
+/// Number of decimal places balances are tracked and reported at.
+pub const SCALE: u32 = 4;
+
+/// How incoming amounts are rounded to [`SCALE`] decimal places.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Round half away from zero (the default); `1.00005` becomes `1.0001`.
+    #[default]
+    HalfUp,
+    /// Round half to even ("banker's rounding").
+    Bankers,
+    /// Truncate toward zero, discarding excess digits.
+    Truncate,
+}
+
+impl Rounding {
+    /// Normalize `amount` to [`SCALE`] decimal places under this strategy.
+    pub fn apply(self, amount: Decimal) -> Decimal {
+        let strategy = match self {
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::Bankers => RoundingStrategy::MidpointNearestEven,
+            Rounding::Truncate => RoundingStrategy::ToZero,
+        };
+
+        amount.round_dp_with_strategy(SCALE, strategy)
+    }
+}
+
 /// Client ID
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(transparent)]
@@ -53,29 +81,36 @@ pub struct AccountOutput {
     pub locked: bool,
 }
 
+/// Return `amount` with its scale pinned to exactly [`SCALE`] decimal places,
+/// padding shorter values so they always render as four-place numbers.
+fn rescaled(mut amount: Decimal) -> Decimal {
+    amount.rescale(SCALE);
+    amount
+}
+
 impl AccountOutput {
     pub fn new(client: ClientId, account: &Account) -> Self {
+        // Pin the reported scale to exactly four places so output is
+        // deterministic regardless of the scale the inputs happened to carry.
+        // `rescale` pads low-scale values (e.g. `0` -> `0.0000`), which
+        // `round_dp` would not.
         Self {
             client: client.0,
-            available: account.available,
-            held: account.held,
-            total: account.total,
+            available: rescaled(account.available),
+            held: rescaled(account.held),
+            total: rescaled(account.total),
             locked: account.locked,
         }
     }
 }
 
 impl Account {
-    pub(crate) fn new() -> Self {
-        Self::default()
-    }
-
     pub(crate) fn deposit(&mut self, amount: Decimal) {
         self.available += amount;
         self.total += amount;
     }
 
-    pub(crate) fn withdraw(&mut self, amount: Decimal) -> Result<()> {
+    pub(crate) fn withdraw(&mut self, amount: Decimal) -> Result<(), EngineError> {
         if self.available >= amount {
             self.available -= amount;
             self.total -= amount;
@@ -83,7 +118,7 @@ impl Account {
             return Ok(());
         }
 
-        bail!("Insufficient funds")
+        Err(EngineError::InsufficientFunds)
     }
 
     pub(crate) fn dispute(&mut self, amount: Decimal) {
@@ -101,4 +136,27 @@ impl Account {
         self.total -= amount;
         self.locked = true;
     }
+
+    /// Disputing a withdrawal provisionally restores the withdrawn funds and
+    /// holds them pending the outcome, rather than moving available funds into
+    /// held as a deposit dispute does.
+    pub(crate) fn dispute_withdrawal(&mut self, amount: Decimal) {
+        self.held += amount;
+        self.total += amount;
+    }
+
+    /// Resolving a disputed withdrawal lets the original withdrawal stand,
+    /// releasing the provisionally restored funds.
+    pub(crate) fn resolve_withdrawal(&mut self, amount: Decimal) {
+        self.held -= amount;
+        self.total -= amount;
+    }
+
+    /// Charging back a disputed withdrawal finalizes the reversal, returning the
+    /// funds to the client as available and freezing the account.
+    pub(crate) fn chargeback_withdrawal(&mut self, amount: Decimal) {
+        self.held -= amount;
+        self.available += amount;
+        self.locked = true;
+    }
 }
@@ -1,9 +1,66 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::{Path, PathBuf};
+use transaction_processor::transaction::Rounding;
+
+/// CLI mirror of [`Rounding`] so the engine crate stays free of clap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RoundingArg {
+    /// Round half away from zero (default).
+    HalfUp,
+    /// Round half to even ("banker's rounding").
+    Bankers,
+    /// Truncate toward zero.
+    Truncate,
+}
+
+impl From<RoundingArg> for Rounding {
+    fn from(arg: RoundingArg) -> Self {
+        match arg {
+            RoundingArg::HalfUp => Rounding::HalfUp,
+            RoundingArg::Bankers => Rounding::Bankers,
+            RoundingArg::Truncate => Rounding::Truncate,
+        }
+    }
+}
+
+/// Selects how processed transactions are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StoreBackend {
+    /// Keep the whole history resident in memory (default).
+    Memory,
+    /// Spill the history to an embedded key-value file on disk.
+    Disk,
+}
+
+/// Default worker count: the available parallelism, or 1 if it can't be
+/// determined.
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
 
 /// Trait for reading configuration parameters
 pub trait Config {
-    fn input_path(&self) -> &Path;
+    /// Input CSV path for batch mode, if one was given.
+    fn input_path(&self) -> Option<&Path>;
+
+    /// Listen address when running in server mode, `None` for batch mode.
+    fn serve_addr(&self) -> Option<&str>;
+
+    /// Number of worker threads: client shards in batch mode, request
+    /// handlers in server mode.
+    fn threads(&self) -> usize;
+
+    /// Strategy for rounding incoming amounts to four decimal places.
+    fn rounding(&self) -> Rounding;
+
+    /// Which transaction storage backend to use.
+    fn store_backend(&self) -> StoreBackend;
+
+    /// Filesystem path for the on-disk store (used only with
+    /// [`StoreBackend::Disk`]).
+    fn store_path(&self) -> &Path;
 }
 
 /// CLI configuration
@@ -14,13 +71,71 @@ pub trait Config {
     version
 )]
 pub struct CliConfig {
-    /// Path to the input CSV file containing transactions
-    #[arg(value_name = "INPUT_FILE")]
-    input_file: PathBuf,
+    /// Path to the input CSV file containing transactions (batch mode)
+    #[arg(value_name = "INPUT_FILE", required_unless_present = "command")]
+    input_file: Option<PathBuf>,
+
+    /// Transaction storage backend
+    #[arg(long, value_enum, default_value_t = StoreBackend::Memory)]
+    store: StoreBackend,
+
+    /// Directory for the on-disk store (only used with `--store disk`)
+    #[arg(long, value_name = "DIR", default_value = "transactions.store")]
+    store_path: PathBuf,
+
+    /// Number of worker threads (defaults to the available CPU count)
+    #[arg(long, default_value_t = default_threads())]
+    threads: usize,
+
+    /// Rounding strategy for amounts exceeding four decimal places
+    #[arg(long, value_enum, default_value_t = RoundingArg::HalfUp)]
+    rounding: RoundingArg,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Subcommands that switch the binary out of the default batch mode.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run as a long-running server, ingesting transactions and serving
+    /// balances over HTTP.
+    Serve(ServeArgs),
+}
+
+/// Arguments for the `serve` subcommand.
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Address to bind the HTTP listener to
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
 }
 
 impl Config for CliConfig {
-    fn input_path(&self) -> &Path {
-        &self.input_file
+    fn input_path(&self) -> Option<&Path> {
+        self.input_file.as_deref()
+    }
+
+    fn serve_addr(&self) -> Option<&str> {
+        match &self.command {
+            Some(Command::Serve(args)) => Some(&args.addr),
+            None => None,
+        }
+    }
+
+    fn threads(&self) -> usize {
+        self.threads
+    }
+
+    fn rounding(&self) -> Rounding {
+        self.rounding.into()
+    }
+
+    fn store_backend(&self) -> StoreBackend {
+        self.store
+    }
+
+    fn store_path(&self) -> &Path {
+        &self.store_path
     }
 }
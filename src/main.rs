@@ -1,11 +1,18 @@
 mod config;
+mod serve;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use config::{CliConfig, Config};
+use config::{CliConfig, Config, StoreBackend};
+use std::collections::HashMap;
 use std::io;
 use tracing::{error, info, warn};
-use transaction_processor::{engine::TransactionEngine, transaction::Transaction};
+use transaction_processor::{
+    engine::TransactionEngine,
+    parallel::ParallelEngine,
+    store::{AccountStore, DiskStore, MemAccountStore, TransactionStore},
+    transaction::{AccountOutput, Transaction},
+};
 
 fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -19,6 +26,11 @@ fn main() -> Result<()> {
 
     let config = CliConfig::parse();
 
+    // Server mode takes over the process instead of running a one-shot batch.
+    if let Some(addr) = config.serve_addr() {
+        return serve::serve(addr, config.threads(), config.rounding());
+    }
+
     match process_transactions(&config) {
         Ok(_) => {
             info!("Processing completed successfully");
@@ -34,45 +46,133 @@ fn main() -> Result<()> {
 }
 
 fn process_transactions<C: Config>(config: &C) -> Result<()> {
-    let mut engine = TransactionEngine::new();
+    match config.store_backend() {
+        // The sharded engine keeps a private in-memory history per shard, so it
+        // cannot spill to disk. Honour `--store disk` over `--threads` rather
+        // than silently falling back to memory (which would defeat the whole
+        // point of the disk backend on a huge input).
+        StoreBackend::Disk => {
+            if config.threads() > 1 {
+                warn!(
+                    "--store disk runs single-threaded; ignoring --threads {}",
+                    config.threads()
+                );
+            }
+            let store = DiskStore::open(config.store_path())?;
+            run(
+                config,
+                TransactionEngine::with_stores(store, MemAccountStore::default())
+                    .with_rounding(config.rounding()),
+            )
+        }
+        // More than one worker: shard by client across threads.
+        StoreBackend::Memory if config.threads() > 1 => run_parallel(config),
+        StoreBackend::Memory => {
+            run(config, TransactionEngine::new().with_rounding(config.rounding()))
+        }
+    }
+}
 
-    let mut reader = csv::ReaderBuilder::new()
+/// Open the configured input as a trimmed CSV reader.
+fn open_reader<C: Config>(config: &C) -> Result<csv::Reader<std::fs::File>> {
+    let path = config
+        .input_path()
+        .context("No input file given for batch mode")?;
+    csv::ReaderBuilder::new()
         .trim(csv::Trim::All)
-        .from_path(config.input_path())
-        .context("Failed to open input file")?;
+        .from_path(path)
+        .context("Failed to open input file")
+}
+
+/// Drive a single `engine` over the CSV at the configured input path, whatever
+/// its backing stores are.
+fn run<C, T, A>(config: &C, mut engine: TransactionEngine<T, A>) -> Result<()>
+where
+    C: Config,
+    T: TransactionStore,
+    A: AccountStore,
+{
+    let mut reader = open_reader(config)?;
 
     let mut processed = 0;
     let mut skipped = 0;
+    let mut skipped_by_category: HashMap<&'static str, u64> = HashMap::new();
 
     for result in reader.deserialize() {
         let transaction: Transaction = match result {
             Ok(tx) => tx,
             Err(e) => {
-                warn!("Failed to parse transaction: {}", e);
+                warn!(category = "parse", "Failed to parse transaction: {}", e);
                 skipped += 1;
+                *skipped_by_category.entry("parse").or_default() += 1;
 
                 continue;
             }
         };
 
         if let Err(e) = engine.process(transaction) {
-            warn!("Transaction processing error: {}", e);
+            warn!(category = e.category(), "Transaction processing error: {}", e);
             skipped += 1;
+            *skipped_by_category.entry(e.category()).or_default() += 1;
         } else {
             processed += 1;
         }
     }
 
+    report(processed, skipped, &skipped_by_category);
+    write_accounts(engine.account_outputs())
+}
+
+/// Drive a client-sharded [`ParallelEngine`] over the input. Routing a client
+/// to a fixed shard keeps per-client ordering while distinct clients run on
+/// separate threads.
+fn run_parallel<C: Config>(config: &C) -> Result<()> {
+    let mut reader = open_reader(config)?;
+    let engine = ParallelEngine::new(config.threads(), config.rounding());
+
+    let mut parse_skipped = 0;
+
+    for result in reader.deserialize() {
+        match result {
+            Ok(transaction) => engine.submit(transaction),
+            Err(e) => {
+                warn!(category = "parse", "Failed to parse transaction: {}", e);
+                parse_skipped += 1;
+            }
+        }
+    }
+
+    let summary = engine.finish();
+    let mut skipped_by_category = summary.skipped_by_category;
+    if parse_skipped > 0 {
+        skipped_by_category.insert("parse", parse_skipped);
+    }
+
+    report(
+        summary.processed,
+        summary.skipped + parse_skipped,
+        &skipped_by_category,
+    );
+    write_accounts(summary.accounts)
+}
+
+/// Log the processed/skipped summary, including the per-category breakdown of
+/// skipped transactions.
+fn report(processed: u64, skipped: u64, skipped_by_category: &HashMap<&'static str, u64>) {
     info!(
+        categories = ?skipped_by_category,
         "Processed {} transactions, skipped {} invalid transactions",
         processed, skipped
     );
+}
 
+/// Serialize the final account states to stdout as CSV.
+fn write_accounts(accounts: Vec<AccountOutput>) -> Result<()> {
     let stdout = io::stdout();
     let handle = stdout.lock();
     let mut writer = csv::WriterBuilder::new().from_writer(handle);
 
-    for account in engine.get_accounts() {
+    for account in accounts {
         writer
             .serialize(account)
             .context("Failed to serialize account")?;
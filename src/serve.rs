@@ -0,0 +1,230 @@
+//! Long-running server mode.
+//!
+//! Wraps a shared [`TransactionEngine`] behind a mutex and exposes it over
+//! HTTP: clients POST transactions to `/ingest` and read balances from
+//! `/accounts` (all clients) or `/accounts/{client}` (one client). The query
+//! endpoints negotiate JSON or CSV via the `Accept` header and always reflect
+//! every transaction accepted so far.
+
+use anyhow::{Context, Result};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+use transaction_processor::{
+    engine::TransactionEngine,
+    transaction::{Rounding, Transaction},
+};
+
+/// A thread-safe engine shared across request handlers.
+type SharedEngine = Arc<Mutex<TransactionEngine>>;
+
+/// Start the HTTP server on `addr` and serve requests until the process is
+/// stopped. Uses `workers` threads to handle requests concurrently; the engine
+/// mutex serializes every ingest and query against the engine.
+pub fn serve(addr: &str, workers: usize, rounding: Rounding) -> Result<()> {
+    let engine: SharedEngine =
+        Arc::new(Mutex::new(TransactionEngine::new().with_rounding(rounding)));
+
+    let server = Server::http(addr)
+        .map_err(|e| anyhow::anyhow!("Failed to bind server to {addr}: {e}"))?;
+    let server = Arc::new(server);
+
+    info!("Listening on {}", addr);
+
+    let mut handles = Vec::new();
+    for _ in 0..workers.max(1) {
+        let server = Arc::clone(&server);
+        let engine = Arc::clone(&engine);
+        handles.push(thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if let Err(e) = handle(request, &engine) {
+                    warn!("Request handling error: {}", e);
+                }
+            }
+        }));
+    }
+
+    for handle in handles {
+        // A worker only returns if the server is torn down.
+        let _ = handle.join();
+    }
+
+    Ok(())
+}
+
+/// Dispatch a single request to the matching endpoint.
+fn handle(request: Request, engine: &SharedEngine) -> Result<()> {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or(&url);
+
+    match (&method, path) {
+        (Method::Post, "/ingest") => ingest(request, engine),
+        (Method::Get, "/accounts") => query_all(request, engine),
+        (Method::Get, p) if p.starts_with("/accounts/") => {
+            let client = p.trim_start_matches("/accounts/");
+            query_one(request, engine, client)
+        }
+        _ => respond(request, Response::from_string("not found").with_status_code(404)),
+    }
+}
+
+/// Accept one or more transactions from the request body. The body may be a
+/// single JSON object, a JSON array, or newline-delimited JSON (one object per
+/// line). Replies with the accepted/rejected counts.
+fn ingest(mut request: Request, engine: &SharedEngine) -> Result<()> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .context("Failed to read request body")?;
+
+    let transactions = match parse_transactions(&body) {
+        Ok(txs) => txs,
+        Err(e) => {
+            return respond(
+                request,
+                Response::from_string(format!("invalid body: {e}")).with_status_code(400),
+            );
+        }
+    };
+
+    let mut accepted = 0u64;
+    let mut rejected = 0u64;
+    {
+        let mut engine = engine.lock().expect("engine mutex poisoned");
+        for transaction in transactions {
+            match engine.process(transaction) {
+                Ok(()) => accepted += 1,
+                Err(e) => {
+                    warn!(category = e.category(), "Rejected transaction: {}", e);
+                    rejected += 1;
+                }
+            }
+        }
+    }
+
+    let payload = format!("{{\"accepted\":{accepted},\"rejected\":{rejected}}}");
+    respond(request, json_response(payload))
+}
+
+/// Parse a request body into transactions, accepting a JSON array, a single
+/// JSON object, or newline-delimited JSON.
+fn parse_transactions(body: &str) -> Result<Vec<Transaction>> {
+    let trimmed = body.trim();
+    if trimmed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if trimmed.starts_with('[') {
+        return serde_json::from_str(trimmed).context("invalid JSON array");
+    }
+
+    if trimmed.starts_with('{') {
+        if !trimmed.contains('\n') {
+            let tx = serde_json::from_str(trimmed).context("invalid JSON object")?;
+            return Ok(vec![tx]);
+        }
+        // A single object may span several lines; otherwise treat as NDJSON.
+        if let Ok(tx) = serde_json::from_str(trimmed) {
+            return Ok(vec![tx]);
+        }
+    }
+
+    trimmed
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line.trim()).context("invalid JSON line"))
+        .collect()
+}
+
+/// Serve every account, in the format requested by the `Accept` header.
+fn query_all(request: Request, engine: &SharedEngine) -> Result<()> {
+    let accounts = engine
+        .lock()
+        .expect("engine mutex poisoned")
+        .account_outputs();
+
+    let response = if wants_csv(&request) {
+        csv_response(&accounts)?
+    } else {
+        json_response(serde_json::to_string(&accounts).context("Failed to encode accounts")?)
+    };
+
+    respond(request, response)
+}
+
+/// Serve a single client's account, 404 if that client has no activity.
+fn query_one(request: Request, engine: &SharedEngine, client: &str) -> Result<()> {
+    let client: u16 = match client.parse() {
+        Ok(c) => c,
+        Err(_) => {
+            return respond(
+                request,
+                Response::from_string("invalid client id").with_status_code(400),
+            );
+        }
+    };
+
+    let account = engine
+        .lock()
+        .expect("engine mutex poisoned")
+        .account_output(client);
+
+    match account {
+        Some(account) => {
+            let response = if wants_csv(&request) {
+                csv_response(std::slice::from_ref(&account))?
+            } else {
+                json_response(serde_json::to_string(&account).context("Failed to encode account")?)
+            };
+            respond(request, response)
+        }
+        None => respond(
+            request,
+            Response::from_string("unknown client").with_status_code(404),
+        ),
+    }
+}
+
+/// Whether the request's `Accept` header asks for CSV.
+fn wants_csv(request: &Request) -> bool {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Accept"))
+        .map(|h| h.value.as_str().contains("text/csv"))
+        .unwrap_or(false)
+}
+
+/// Build a JSON response with the appropriate content type.
+fn json_response(body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("valid header");
+    Response::from_string(body).with_header(header)
+}
+
+/// Serialize accounts to CSV and wrap them in a `text/csv` response.
+fn csv_response(
+    accounts: &[transaction_processor::transaction::AccountOutput],
+) -> Result<Response<std::io::Cursor<Vec<u8>>>> {
+    let mut writer = csv::WriterBuilder::new().from_writer(Vec::new());
+    for account in accounts {
+        writer
+            .serialize(account)
+            .context("Failed to serialize account")?;
+    }
+    let bytes = writer.into_inner().context("Failed to flush CSV")?;
+
+    let header =
+        Header::from_bytes(&b"Content-Type"[..], &b"text/csv"[..]).expect("valid header");
+    Ok(Response::from_data(bytes).with_header(header))
+}
+
+/// Send a response, returning an error if writing it fails.
+fn respond<R: std::io::Read>(request: Request, response: Response<R>) -> Result<()> {
+    request
+        .respond(response)
+        .context("Failed to write response")
+}